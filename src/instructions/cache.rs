@@ -0,0 +1,135 @@
+//! Functions to manage the CPU caches.
+
+/// Writes back all modified cache lines to main memory and invalidates (flushes) the
+/// internal caches.
+///
+/// After issuing `WBINVD`, the processor does not wait for external caches to complete
+/// their invalidation before continuing execution, so system software may need additional
+/// synchronization when external caches are present.
+///
+/// ## Safety
+///
+/// This instruction is a privileged instruction meant to be used by the kernel. It can be
+/// very slow, as it invalidates the *entire* cache hierarchy, and must only be used where
+/// this is actually intended, e.g. when reconfiguring memory types.
+#[inline]
+pub unsafe fn wbinvd() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("wbinvd" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_wbinvd();
+    }
+}
+
+/// Invalidates the CPU's internal caches without writing back modified cache lines to
+/// memory first.
+///
+/// ## Safety
+///
+/// This is an extremely unsafe operation: any dirty cache lines are discarded rather than
+/// written back, so any data that only exists in cache (and not yet in memory) is lost.
+/// It must only ever be used in very specific situations, such as immediately before a
+/// system reset, where the loss of cached data is acceptable or even desired.
+#[inline]
+pub unsafe fn invd() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("invd" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_invd();
+    }
+}
+
+/// Flushes the cache line that contains `addr` from all levels of the cache hierarchy.
+///
+/// ## Safety
+///
+/// The caller must ensure that flushing the given address is safe, e.g. that it does not
+/// race with a concurrent write that assumes the line will remain in cache.
+#[inline]
+pub unsafe fn clflush(addr: *const u8) {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("clflush ($0)" :: "r"(addr) : "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_clflush(addr);
+    }
+}
+
+/// Flushes the cache line that contains `addr` from all levels of the cache hierarchy, using
+/// a weaker memory ordering than [`clflush`].
+///
+/// ## Safety
+///
+/// In addition to the safety requirements of [`clflush`], the caller is responsible for
+/// checking that the CPU actually supports `CLFLUSHOPT` (via `CPUID.(EAX=7,ECX=0):EBX[23]`)
+/// before calling this function, as this crate does not check CPUID feature bits for the
+/// caller.
+#[inline]
+pub unsafe fn clflushopt(addr: *const u8) {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("clflushopt ($0)" :: "r"(addr) : "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_clflushopt(addr);
+    }
+}
+
+/// Performs a serializing operation on all load-from-memory and store-to-memory
+/// instructions that were issued prior to this instruction, guaranteeing that every load and
+/// store preceding `mfence` is globally visible before any load or store following it.
+#[inline]
+pub fn mfence() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("mfence" ::: "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_mfence();
+    }
+}
+
+/// Performs a serializing operation on all store-to-memory instructions that were issued
+/// prior to this instruction.
+#[inline]
+pub fn sfence() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("sfence" ::: "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_sfence();
+    }
+}
+
+/// Performs a serializing operation on all load-from-memory instructions that were issued
+/// prior to this instruction.
+#[inline]
+pub fn lfence() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("lfence" ::: "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_lfence();
+    }
+}
@@ -2,6 +2,7 @@
 
 //! Special x86_64 instructions.
 
+pub mod cache;
 pub mod interrupts;
 pub mod port;
 pub mod random;
@@ -165,3 +166,281 @@ pub unsafe fn rdgsbase() -> u64 {
 
     inner()
 }
+
+/// Clears the task-switched (`CR0.TS`) flag.
+///
+/// This is typically called from the device-not-available (`#NM`) exception handler right
+/// before the handler restores the FPU/SSE state of the task that caused the exception, so
+/// that the task can resume executing FPU/SSE instructions without faulting again.
+///
+/// ## Safety
+///
+/// The caller must ensure that the FPU/SSE state belonging to the previously running task
+/// has already been saved (or does not need saving), and that the state of the task being
+/// resumed is restored before it is allowed to execute any FPU/SSE instruction.
+#[inline]
+pub unsafe fn clts() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("clts" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_clts();
+    }
+}
+
+/// Sets the task-switched (`CR0.TS`) flag.
+///
+/// This is usually done on a context switch, so that the next task that touches the FPU/SSE
+/// state triggers a device-not-available (`#NM`) exception, giving the kernel a chance to
+/// lazily save and restore per-task FPU/SSE state instead of doing so unconditionally on
+/// every switch.
+///
+/// ## Safety
+///
+/// The caller must ensure that setting `CR0.TS` does not interfere with FPU/SSE state that
+/// is currently in use, e.g. that no FPU/SSE instructions are executed by the current task
+/// between this call and the next task switch.
+#[inline]
+pub unsafe fn stts() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        let cr0: u64;
+        llvm_asm!("mov %cr0, $0" : "=r"(cr0) ::: "volatile");
+        llvm_asm!("mov $0, %cr0" :: "r"(cr0 | 0x8) : "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_stts();
+    }
+}
+
+/// Sets the `RFLAGS.AC` bit, temporarily allowing supervisor-mode accesses to user-mode
+/// pages even when `CR4.SMAP` is enabled.
+///
+/// ## Safety
+///
+/// This bit only has any effect while `CR4.SMAP` is set; callers are responsible for pairing
+/// every `stac()` with a matching [`clac`] once the user memory access is complete, and for
+/// not leaving the access window open longer than necessary. See [`SmapGuard`] for a safe
+/// scoped wrapper around this pair of calls.
+#[inline]
+pub unsafe fn stac() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("stac" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_stac();
+    }
+}
+
+/// Clears the `RFLAGS.AC` bit, re-enabling SMAP enforcement against user-mode pages.
+///
+/// ## Safety
+///
+/// The caller must ensure that all accesses to user-mode memory made under the preceding
+/// [`stac`] call have completed, as accesses made after `clac()` will fault under SMAP.
+#[inline]
+pub unsafe fn clac() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("clac" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_clac();
+    }
+}
+
+/// An RAII guard that permits supervisor access to user-mode pages under SMAP for the
+/// duration of its lifetime.
+///
+/// Constructing a `SmapGuard` executes [`stac`], and dropping it executes [`clac`], so a
+/// user-copy routine can simply write:
+///
+/// ```ignore
+/// let _guard = SmapGuard::new();
+/// // touch user memory here
+/// ```
+///
+/// ## Safety
+///
+/// `SmapGuard` only makes sense to use while `CR4.SMAP` is enabled; on kernels that leave
+/// `CR4.SMAP` clear it is harmless but unnecessary. On CPUs without SMAP support, `stac` and
+/// `clac` raise `#UD`, so the guard must not be used there. Nested guards are only correct if
+/// the outermost guard is the one that owns the original `RFLAGS.AC` state: since `Drop`
+/// always runs `clac()` unconditionally, a nested guard being dropped first will clear `AC`
+/// while the outer guard still expects user access to be permitted. Do not nest `SmapGuard`s
+/// unless the outer guard makes no user memory access after the inner one is dropped.
+#[must_use]
+pub struct SmapGuard {
+    _private: (),
+}
+
+impl SmapGuard {
+    /// Creates a new `SmapGuard`, enabling supervisor access to user-mode pages by calling
+    /// [`stac`].
+    ///
+    /// Deliberately has no `Default` impl: constructing a `SmapGuard` is not a side-effect-free
+    /// operation (it executes `stac`, toggling `RFLAGS.AC`), so it should not be reachable
+    /// through an ambient, safety-comment-free entry point like `SmapGuard::default()`.
+    #[inline]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        unsafe {
+            stac();
+        }
+        SmapGuard { _private: () }
+    }
+}
+
+impl Drop for SmapGuard {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            clac();
+        }
+    }
+}
+
+/// Exchanges the current `GSBASE` with the value in the `IA32_KERNEL_GS_BASE` MSR.
+///
+/// Syscall and interrupt entry stubs use this to swap in the kernel's GS base (which points
+/// at kernel-private per-CPU data) on entry from user mode, and `swapgs` again to restore the
+/// user GS base before returning with `sysret`/`iretq`.
+///
+/// ## Safety
+///
+/// The caller must ensure that `swapgs` is paired correctly: an entry stub must call it
+/// exactly once on kernel entry and exactly once before returning to user mode. If a fault
+/// or interrupt occurs while already running with the kernel GS base loaded (e.g. a nested
+/// fault in the middle of an entry stub, before the kernel knows whether `swapgs` has
+/// already run), calling it unconditionally will swap again and leave `GSBASE` pointing at
+/// user-controlled (or otherwise wrong) data, corrupting any subsequent per-CPU access. See
+/// [`rdgsbase`]/[`wrgsbase`] for the FSGSBASE-based alternative, which should be preferred
+/// over the `IA32_KERNEL_GS_BASE` swap when `CR4.FSGSBASE` is available and a single GS base
+/// load (rather than the MSR exchange) is all that is needed.
+#[inline]
+pub unsafe fn swapgs() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("swapgs" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_swapgs();
+    }
+}
+
+/// Sets up address-range monitoring on the cache line containing `addr`, arming the
+/// monitor hardware used by [`mwait`].
+///
+/// `extensions` and `hints` are passed through to `MONITOR` in `ECX`/`EDX` respectively; both
+/// are currently reserved by the architecture and should be `0`.
+///
+/// ## Safety
+///
+/// `CPUID.01H:ECX.MONITOR` must be set for this instruction to be available. The canonical
+/// idle pattern is to write a flag, call `monitor` on its address, re-check the flag (to
+/// avoid a lost wakeup if it changed between the write and the `monitor` call), and only then
+/// call [`mwait`]:
+///
+/// ```ignore
+/// flag.store(false, Ordering::SeqCst);
+/// monitor(&flag as *const _ as *const u8, 0, 0);
+/// if !flag.load(Ordering::SeqCst) {
+///     mwait(0, 0);
+/// }
+/// ```
+#[inline]
+pub unsafe fn monitor(addr: *const u8, extensions: u32, hints: u32) {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("monitor" :: "{rax}"(addr), "{ecx}"(extensions), "{edx}"(hints) : "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_monitor(addr, extensions, hints);
+    }
+}
+
+/// Enters an implementation-dependent optimized (idle) state until the address range armed
+/// by a preceding [`monitor`] call is written to, or another wake event occurs.
+///
+/// `hints` (`EAX`) encodes the target state to enter, and `extensions` (`ECX`) carries
+/// `MWAIT` extensions, e.g. bit 0 set treats a pending interrupt as a wake event even while
+/// interrupts are masked.
+///
+/// ## Safety
+///
+/// `CPUID.01H:ECX.MONITOR` must be set, and this call must be preceded by a matching
+/// [`monitor`] call (with the flag re-checked in between, see its documentation) or the wait
+/// may never wake up on the intended condition.
+#[inline]
+pub unsafe fn mwait(hints: u32, extensions: u32) {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("mwait" :: "{eax}"(hints), "{ecx}"(extensions) : "memory" : "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_mwait(hints, extensions);
+    }
+}
+
+/// Emits the `PAUSE` hint, which improves the performance of spin-wait loops.
+///
+/// On a hyperthreaded core, `PAUSE` also hints to the CPU that the current logical processor
+/// is spinning so it can yield execution resources to a sibling logical processor. It should
+/// be placed in the body of read-spin loops, e.g. `while flag.load(Ordering::Acquire) { pause(); }`.
+///
+/// This instruction has no side effects and is therefore safe to call.
+#[inline]
+pub fn pause() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("pause" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_pause();
+    }
+}
+
+/// Executes the `SERIALIZE` instruction, which drains the pipeline so that all modifications
+/// to flags, registers, and memory by previous instructions are completed before the next
+/// instruction is fetched and executed.
+///
+/// This is stronger than [`mfence`](cache::mfence)/[`lfence`](cache::lfence)/[`sfence`](cache::sfence),
+/// which only order memory accesses: `serialize` also prevents speculative execution across
+/// the barrier, which matters before reading high-resolution timers or switching privilege
+/// state where speculatively executed instructions could leak information.
+///
+/// ## Safety
+///
+/// `CPUID.(EAX=7,ECX=0):EDX[14]` must be set for `SERIALIZE` to be available; the caller is
+/// responsible for checking this, as this crate does not check CPUID feature bits for the
+/// caller.
+#[inline]
+pub unsafe fn serialize() {
+    #[cfg(feature = "inline_asm")]
+    unsafe {
+        llvm_asm!("serialize" :::: "volatile");
+    }
+
+    #[cfg(not(feature = "inline_asm"))]
+    unsafe {
+        crate::asm::x86_64_asm_serialize();
+    }
+}